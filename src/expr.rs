@@ -1,17 +1,42 @@
 use std::fmt::{Display, self};
 
-use crate::scanner::{Literal, TokenType};
+use crate::{scanner::Literal, interner::Symbol};
 
 pub trait Visitor {
     // fn visit_binary_expr(expr: Expr) -> String;
     fn accept(&self, visitor: Box<dyn Visitor>);
 }
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Expr {
     Literal(Literal),
     Unary(Box<Expr>, Box<Expr>),
     Binary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
     Grouping(Box<Expr>),
+    Interpolation(Vec<Expr>),
+    Variable(Symbol),
+    Get(Box<Expr>, Symbol),
+    Call(Box<Expr>, Vec<Expr>),
+    List(Vec<Expr>),
+    // `{key: value, ...}`. Keys must evaluate to nil, a boolean, a number or
+    // a string; lists, maps, functions and instances are not hashable.
+    Map(Vec<(Expr, Expr)>),
+    // `object[index]`; a negative index counts back from the end
+    Index(Box<Expr>, Box<Expr>),
+    // `object[start:end]`, where either bound may be left out
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    // `target = value`, or `target op= value` when an operator is given. The
+    // target is a Variable, Get or Index, and its object and index are
+    // evaluated only once, so `a.b().c += 1` calls `a.b()` a single time.
+    Assign(Box<Expr>, Option<BinaryOperatorEnum>, Box<Expr>),
+    // `++`/`--` on a Variable, Get or Index target, with the same single evaluation
+    Increment(Box<Expr>, IncrementOperator),
+    // `(a, b) => body`, closing over the variables it mentions
+    Lambda(Vec<Symbol>, Box<Expr>),
+    // `match value { pattern if guard => body, ... }`. The first arm whose
+    // pattern matches and whose guard holds is evaluated; no match is a
+    // runtime error.
+    Match(Box<Expr>, Vec<MatchArm>),
     BinaryOp(BinaryOperator),
     UnaryOp(UnaryOperator)
 }
@@ -20,12 +45,45 @@ impl Expr {
     pub fn visit(expr: Expr) -> String {
         match expr {
             Expr::Binary(left, operator, right) => Expr::parenthesize("Binary Operation".to_string(), vec![*left, *operator, *right]),
+            Expr::Conditional(condition, then_branch, else_branch) => Expr::parenthesize("Conditional Operation".to_string(), vec![*condition, *then_branch, *else_branch]),
             Expr::Unary(operator, expression) => Expr::parenthesize("Unary Operation".to_string(), vec![*operator, *expression]),
             Expr::Grouping(group) => Expr::parenthesize("Grouping Operation".to_string(), vec![*group]),
+            Expr::Interpolation(parts) => Expr::parenthesize("Interpolation".to_string(), parts),
+            Expr::Variable(name) => name.to_string(),
+            Expr::Get(object, name) => format!("(Property Access{}.{})", Expr::visit(*object), name),
+            Expr::Call(callee, arguments) => Expr::parenthesize("Call".to_string(), std::iter::once(*callee).chain(arguments).collect()),
+            Expr::List(elements) => Expr::parenthesize("List".to_string(), elements),
+            Expr::Map(entries) => {
+                let entries: Vec<String> = entries.into_iter().map(|(key, value)| format!("{}:{}", Expr::visit(key), Expr::visit(value))).collect();
+                format!("(Map{})", entries.join(","))
+            },
+            Expr::Index(object, index) => Expr::parenthesize("Index".to_string(), vec![*object, *index]),
+            Expr::Slice(object, start, end) => {
+                let bound = |bound: Option<Box<Expr>>| bound.map_or(String::new(), |b| Expr::visit(*b));
+                format!("(Slice{}[{}:{}])", Expr::visit(*object), bound(start), bound(end))
+            },
+            Expr::Assign(target, operator, value) => {
+                let name = match operator {
+                    Some(operator) => format!("Assignment{}=", operator),
+                    None => "Assignment=".to_string(),
+                };
+                Expr::parenthesize(name, vec![*target, *value])
+            },
+            Expr::Increment(target, operator) => Expr::parenthesize(format!("Increment{}", operator), vec![*target]),
+            Expr::Lambda(params, body) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                format!("(Lambda({}){})", params.join(","), Expr::visit(*body))
+            },
+            Expr::Match(value, arms) => {
+                let arms: Vec<String> = arms.into_iter().map(|arm| {
+                    let guard = arm.guard.map_or(String::new(), |guard| format!(" if {}", Expr::visit(guard)));
+                    format!("[{}{} => {}]", arm.pattern, guard, Expr::visit(arm.body))
+                }).collect();
+                format!("(Match{}{})", Expr::visit(*value), arms.join(""))
+            },
             Expr::Literal(literal) => format!("{}", literal),
             Expr::BinaryOp(operator) => operator.token_type.to_string(),
             Expr::UnaryOp(operator) => operator.token_type.to_string(),
-            _ => "".to_string()
         }
     }
 
@@ -40,12 +98,12 @@ impl Expr {
 }
 
 impl Visitor for Expr {
-    fn accept(&self, visitor: Box<dyn Visitor>) {
+    fn accept(&self, _visitor: Box<dyn Visitor>) {
         todo!()
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum BinaryOperatorEnum {
     EqualEqual,
     NotEqual,
@@ -57,6 +115,22 @@ pub enum BinaryOperatorEnum {
     Minus,
     Star,
     Slash,
+    // `%` truncates like C's fmod: the result takes the sign of the dividend
+    Percent,
+    // `**` is right-associative and binds tighter than unary minus
+    StarStar,
+    // The bitwise operators only accept numbers that are exact integers and
+    // work on their 64-bit two's complement representation.
+    Ampersand,
+    Pipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
+    // `a..b` excludes `b` and `a..=b` includes it; both bind looser than
+    // arithmetic and tighter than comparison
+    DotDot,
+    DotDotEqual,
+    Comma,
 }
 
 impl Display for BinaryOperatorEnum {
@@ -72,28 +146,39 @@ impl Display for BinaryOperatorEnum {
             Self::Minus => write!(f, "-"),
             Self::Star => write!(f, "*"),
             Self::Slash => write!(f, "/"),
+            Self::Percent => write!(f, "%"),
+            Self::StarStar => write!(f, "**"),
+            Self::Ampersand => write!(f, "&"),
+            Self::Pipe => write!(f, "|"),
+            Self::Caret => write!(f, "^"),
+            Self::LessLess => write!(f, "<<"),
+            Self::GreaterGreater => write!(f, ">>"),
+            Self::DotDot => write!(f, ".."),
+            Self::DotDotEqual => write!(f, "..="),
+            Self::Comma => write!(f, ","),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct BinaryOperator {
     pub token_type: BinaryOperatorEnum,
-    pub lexme: String,
+    pub lexme: Symbol,
     pub literal: Option<Literal>,
     pub line: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum UnaryOperatorEnum {
     Minus,
-    Bang
+    Bang,
+    Tilde,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct UnaryOperator {
     pub token_type: UnaryOperatorEnum,
-    pub lexme: String,
+    pub lexme: Symbol,
     pub literal: Option<Literal>,
     pub line: usize,
 }
@@ -103,6 +188,75 @@ impl Display for UnaryOperatorEnum {
         match self {
             Self::Minus => write!(f, "-"),
             Self::Bang => write!(f, "!"),
+            Self::Tilde => write!(f, "~"),
         }
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IncrementOperator {
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+impl Display for IncrementOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PreIncrement => write!(f, "++_"),
+            Self::PreDecrement => write!(f, "--_"),
+            Self::PostIncrement => write!(f, "_++"),
+            Self::PostDecrement => write!(f, "_--"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pattern {
+    // `_` matches anything and binds nothing
+    Wildcard,
+    Binding(Symbol),
+    // Compared with `==`
+    Literal(Literal),
+    // `start..end`, or `start..=end` when the bool is set
+    Range(Literal, Literal, bool),
+    // `[a, b, c]` matches lists of exactly that length
+    List(Vec<Pattern>),
+    // `Point { x, y: 0 }` matches instances of the class `Point`. A field
+    // without a pattern binds a variable of the same name.
+    Instance(Symbol, Vec<(Symbol, Pattern)>),
+}
+
+impl Pattern {
+    // Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Self::Wildcard | Self::Binding(_))
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => write!(f, "_"),
+            Self::Binding(name) => write!(f, "{}", name),
+            Self::Literal(literal) => write!(f, "{}", literal),
+            Self::Range(start, end, inclusive) => write!(f, "{}{}{}", start, if *inclusive { "..=" } else { ".." }, end),
+            Self::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            },
+            Self::Instance(class, fields) if fields.is_empty() => write!(f, "{} {{}}", class),
+            Self::Instance(class, fields) => {
+                let fields: Vec<String> = fields.iter().map(|(name, pattern)| format!("{}: {}", name, pattern)).collect();
+                write!(f, "{} {{ {} }}", class, fields.join(", "))
+            },
+        }
+    }
+}
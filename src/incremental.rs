@@ -0,0 +1,271 @@
+use std::ops::Range;
+
+use crate::{expr::Expr, parser::{Parser, ParserError}, scanner::{Lexer, Literal, ScanError, Token, TokenType}};
+
+// The lexer peeks at most two chars past the end of a token.
+const LOOKAHEAD_BYTES: usize = 2 * 4;
+
+// A token stored by byte position so it can outlive edits to the source.
+#[derive(Debug, Clone, PartialEq)]
+struct SpanToken {
+    token_type: TokenType,
+    start: usize,
+    end: usize,
+    literal: Option<Literal>,
+    line: usize,
+}
+
+// One result pulled from the lexer, with the lexer state it was scanned from.
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    offset: usize,
+    line: usize,
+    top_level: bool,
+    result: Result<SpanToken, ScanError>,
+}
+
+impl Item {
+    fn shift(&mut self, delta: isize, line_delta: isize) {
+        self.offset = self.offset.wrapping_add_signed(delta);
+        self.line = self.line.wrapping_add_signed(line_delta);
+        match &mut self.result {
+            Ok(token) => {
+                token.start = token.start.wrapping_add_signed(delta);
+                token.end = token.end.wrapping_add_signed(delta);
+                token.line = token.line.wrapping_add_signed(line_delta);
+            },
+            Err(e) => e.line = e.line.wrapping_add_signed(line_delta),
+        }
+    }
+}
+
+// A source file kept alongside its scanned tokens so that text edits only
+// re-scan the region they touch. The expression grammar makes every node
+// depend on all tokens, so parsing always runs over the whole token list,
+// but that list is mostly reused.
+pub struct Document {
+    source: String,
+    items: Vec<Item>,
+}
+
+impl Document {
+    pub fn new(source: String) -> Self {
+        let mut items = Vec::new();
+        let mut lexer = Lexer::new(&source);
+        while let Some(item) = Self::next_item(&mut lexer) {
+            items.push(item);
+        }
+        Self { source, items }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Replaces the bytes in `range` with `replacement`, re-scanning from the
+    // last token the edit cannot have influenced until the new tokens line up
+    // with old ones again. Returns the indices of the re-scanned tokens.
+    //
+    // Like `String::replace_range`, panics if `range` is out of bounds or
+    // does not start and end on char boundaries.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Range<usize> {
+        self.source.replace_range(range.clone(), replacement);
+        let delta = replacement.len() as isize - range.len() as isize;
+        let new_end = range.start + replacement.len();
+
+        let restart = self.items.iter()
+            .rposition(|item| item.top_level && item.offset + LOOKAHEAD_BYTES <= range.start)
+            .unwrap_or(0);
+        let mut old = self.items.split_off(restart);
+        let resume = &old[0];
+        let mut lexer = Lexer::resume(&self.source, resume.offset, resume.line);
+
+        loop {
+            let offset = lexer.offset();
+            if offset >= new_end && lexer.is_top_level() {
+                let old_offset = offset.wrapping_add_signed(-delta);
+                if let Ok(i) = old.binary_search_by_key(&old_offset, |item| item.offset) {
+                    if old[i].top_level {
+                        let rescanned = restart..self.items.len();
+                        let line_delta = lexer.line() as isize - old[i].line as isize;
+                        for mut item in old.drain(i..) {
+                            item.shift(delta, line_delta);
+                            self.items.push(item);
+                        }
+                        return rescanned;
+                    }
+                }
+            }
+
+            let item = Self::next_item(&mut lexer).unwrap();
+            let is_eof = matches!(&item.result, Ok(token) if token.token_type == TokenType::Eof);
+            self.items.push(item);
+            if is_eof {
+                return restart..self.items.len();
+            }
+        }
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = Result<Token<'_>, ScanError>> + '_ {
+        self.items.iter().map(move |item| match &item.result {
+            Ok(token) => Ok(Token {
+                token_type: token.token_type,
+                lexme: &self.source[token.start..token.end],
+                literal: token.literal,
+                line: token.line,
+                leading_trivia: "",
+                trailing_trivia: "",
+            }),
+            Err(e) => Err(e.clone()),
+        })
+    }
+
+    // Scan errors for the whole source, in order.
+    pub fn scan_errors(&self) -> impl Iterator<Item = &ScanError> + '_ {
+        self.items.iter().filter_map(|item| item.result.as_ref().err())
+    }
+
+    // Nothing is printed: the syntax error or expression is returned with
+    // any warnings, and scan errors are available from `scan_errors` even
+    // when parsing stops before them.
+    pub fn parse(&self) -> (Result<Expr, ParserError>, Vec<ParserError>) {
+        let mut parser = Parser::new(self.tokens());
+        let result = parser.try_parse();
+        (result, parser.warnings().to_vec())
+    }
+
+    fn next_item(lexer: &mut Lexer) -> Option<Item> {
+        let offset = lexer.offset();
+        let line = lexer.line();
+        let top_level = lexer.is_top_level();
+
+        let result = lexer.next()?;
+        let end = lexer.offset();
+        Some(Item {
+            offset,
+            line,
+            top_level,
+            result: result.map(|token| SpanToken {
+                token_type: token.token_type,
+                start: end - token.lexme.len(),
+                end,
+                literal: token.literal,
+                line: token.line,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks that the document is exactly what scanning its source from
+    // scratch produces, down to the lexer state stored with each token.
+    fn assert_fresh(doc: &Document) {
+        let fresh = Document::new(doc.source().to_string());
+        assert_eq!(doc.items, fresh.items, "after editing to {:?}", doc.source());
+        assert!(doc.tokens().eq(Lexer::new(doc.source())), "after editing to {:?}", doc.source());
+        let mut parser = Parser::new(Lexer::new(doc.source()));
+        let parsed = (parser.try_parse(), parser.warnings().to_vec());
+        assert_eq!(doc.parse(), parsed, "after editing to {:?}", doc.source());
+    }
+
+    // Replaces the first `from` with `to`, trimmed to the smallest edit so
+    // that the restart point is as close to the change as possible.
+    fn replace(doc: &mut Document, from: &str, to: &str) {
+        let start = doc.source().find(from).unwrap();
+        let prefix = from.char_indices().zip(to.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, c), _)| i + c.len_utf8());
+        let suffix = from[prefix..].chars().rev().zip(to[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum::<usize>();
+        doc.edit(start + prefix..start + from.len() - suffix, &to[prefix..to.len() - suffix]);
+        assert_fresh(doc);
+    }
+
+    fn check(source: &str, edits: &[(&str, &str)]) {
+        let mut doc = Document::new(source.to_string());
+        assert_fresh(&doc);
+        for (from, to) in edits {
+            replace(&mut doc, from, to);
+        }
+    }
+
+    #[test]
+    fn edits_inside_interpolation() {
+        check("\"a${b + c}d\" + e", &[
+            ("b + c", "b + \"x\" + c"),
+            ("c}", "c}}"),
+            ("c}}", "c"),
+            ("${", "$"),
+            ("$b", "${b"),
+            ("\"x\"", "\"${x}\""),
+        ]);
+    }
+
+    #[test]
+    fn edits_inside_block_comment() {
+        check("1 /* x */ + 2 /* y */", &[
+            (" x ", " /* x "),
+            ("x */", "x */ */"),
+            ("*/ +", "+"),
+            ("x */ +", "x */ */ +"),
+            ("/* y */", "/* y"),
+        ]);
+    }
+
+    #[test]
+    fn edits_next_to_fraction() {
+        check("a + 1.5", &[
+            ("1.5", "10.5"),
+            ("10.5", ".5"),
+            (".5", "1 .5"),
+            ("1 .5", "1.5"),
+            ("1.5", "1..5"),
+            ("1..5", "1.x5"),
+        ]);
+    }
+
+    #[test]
+    fn edits_with_crlf() {
+        check("a\r\n+ b\r\n* c", &[
+            ("+ b", "+ \r\n\r\nb"),
+            ("\r\n* c", "\n* c"),
+            ("a\r\n", "a"),
+            ("b\n", "b\r\n\"x\r\ny\"\r\n"),
+        ]);
+    }
+
+    #[test]
+    fn edits_with_non_ascii() {
+        check("\"héllo\" + naïve * 日本", &[
+            ("é", "e"),
+            ("naïve", "naïveté"),
+            ("日本", "日x本"),
+            ("\"hello\"", "\"h${ï}o\""),
+            ("ï}", "ï"),
+        ]);
+    }
+
+    // Types each character into every position of each source and deletes
+    // it again, checking against a fresh scan after every keystroke.
+    #[test]
+    fn single_character_edits() {
+        let sources = ["\"a${b}c\" + 1.5", "x /* y */ * z\r\n// w\n-é", "r\"raw\" + 0x1F"];
+        for source in sources {
+            let mut doc = Document::new(source.to_string());
+            for (i, _) in source.char_indices().chain([(source.len(), ' ')]) {
+                for c in ["\"", "}", "{", "$", "/", "*", ".", "5", "\n", "é"] {
+                    doc.edit(i..i, c);
+                    assert_fresh(&doc);
+                    doc.edit(i..i + c.len(), "");
+                    assert_fresh(&doc);
+                }
+            }
+        }
+    }
+}
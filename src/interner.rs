@@ -0,0 +1,64 @@
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner::default());
+}
+
+// An interned string. Two symbols are equal iff their strings are equal, so
+// comparing names is a single integer comparison.
+#[derive(Eq, PartialEq, Hash, Copy, Clone, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Symbol {
+        INTERNER.lock().unwrap().intern(s)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().resolve(self)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Interned strings are leaked so that resolving a symbol can hand out a
+// `&'static str` without holding the lock. Nothing is ever freed, so memory
+// grows with the number of distinct identifiers and string constants the
+// process has seen. That is fine for running a script, but a long-lived
+// `incremental::Document` interns every partial name and string typed into
+// it (`f`, `fo`, `foo`, ...) for as long as the process runs.
+#[derive(Default)]
+struct Interner {
+    map: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&sym) = self.map.get(s) {
+            return sym;
+        }
+
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        let sym = Symbol(self.strings.len() as u32);
+        self.strings.push(s);
+        self.map.insert(s, sym);
+        sym
+    }
+
+    fn resolve(&self, sym: Symbol) -> &'static str {
+        self.strings[sym.0 as usize]
+    }
+}
@@ -0,0 +1,22 @@
+pub mod scanner;
+pub mod expr;
+pub mod parser;
+pub mod interner;
+pub mod incremental;
+pub mod source;
+
+pub static mut HAD_ERROR: bool = false;
+
+pub fn error(line: u32, message: &str) {
+    report(line, "", message);
+}
+
+pub fn report(line: u32, location: &str, message: &str) {
+    eprintln!("[line {}] Error{}: {}", line, location, message);
+    unsafe { HAD_ERROR = true; }
+}
+
+// Warnings are printed like errors but do not stop the script from running.
+pub fn warn(line: u32, location: &str, message: &str) {
+    eprintln!("[line {}] Warning{}: {}", line, location, message);
+}
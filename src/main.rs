@@ -1,75 +1,28 @@
-use std::{process, fs::File, io::{BufReader, self, Write, Read}};
+use std::{env, process, io::{self, Write}};
 
+use rlox::{error, expr::Expr, parser::Parser, scanner::Lexer, source::{SourceError, SourceFile}, HAD_ERROR};
 
-
-use parser::Parser;
-
-use crate::expr::Expr;
-
-mod scanner;
-mod expr;
-mod parser;
-
-static mut HAD_ERROR: bool = false;
 fn main() {
-    let mut s = scanner::Scanner::default();
-    let mut vecs = s.scan_tokens("!( ((4 * 5) / (1 / 2)) == 2);".to_string());
-    // for tok in vecs {
-    //     println!("{:?}", tok);
-    // }
-
-    let mut parser = Parser{
-        start: 0,
-        current: 0,
-        tokens: vecs.to_vec(),
-    };
-
-    let expr = parser.parse();
-    dbg!("{:?}", expr);
-
-    // let expression = Expr::Binary(
-    //     Box::new(Expr::Unary(
-    //         Box::new(Expr::UnaryOp(expr::UnaryOperator::Minus)), Box::new(Expr::Literal(scanner::Literal::Num(123_f64))))), 
-    //     Box::new(Expr::BinaryOp(expr::BinaryOperator::Star)), Box::new(Expr::Grouping(Box::new(Expr::Literal(scanner::Literal::Num(45.67))))));
-    
-    // println!("{}", Expr::visit(expression));
-
-    // let mut s = scanner::Scanner::default();
-    // s.scan_tokens("()()".to_string());
-    // for i in &s.tokens {
-    //     println!("{} => {}", i.lexme, i.token_type);
-    // }
-
-    // let args: Vec<String> = env::args().collect();
-    // println!("{}", args.len());
-    // if args.len() > 2 {
-    //     dbg!("Too many arguments");
-    //     process::exit(0b1000000);
-    // } else if args.len() == 2 {
-    //     run_file(&args[1]).ok();
-    // } else {
-    //     run_prompt();
-    // }
-}
-
-pub fn convert_bytes_to_string(buffer: &Vec<u8>) -> &str {
-     match std::str::from_utf8(buffer) {
-        Ok(v) => v,
-        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 2 {
+        eprintln!("Usage: rlox [script]");
+        process::exit(0b1000000);
+    } else if args.len() == 2 {
+        if let Err(e) = run_file(&args[1]) {
+            eprintln!("{}: {}", args[1], e);
+            process::exit(0b1000000);
+        }
+    } else {
+        run_prompt();
     }
 }
 
 pub fn run_file(path: &String) -> io::Result<()>{
-
-    // reads file `f` into a vector of bytes
-    let f = File::open(path)?;
-    let mut reader = BufReader::new(f);
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
-
-    // converts vector of bytes into a string
-    let s = convert_bytes_to_string(&buffer);
-    run(s.to_owned());
+    match SourceFile::load(path) {
+        Ok(source) => run(source.text),
+        Err(SourceError::Io(e)) => return Err(e),
+        Err(e @ SourceError::InvalidUtf8 { line, .. }) => error(line.try_into().unwrap(), &e.to_string()),
+    }
 
     if unsafe { HAD_ERROR } {
         process::exit(0b1000000);
@@ -96,10 +49,8 @@ pub fn run_prompt() {
 }
 
 pub fn run(s: String) {
-    // todo!("Implement run");
-    print!("{}", s);
+    if let Some(expr) = Parser::new(Lexer::new(&s)).parse() {
+        println!("{}", Expr::visit(expr));
+    }
 }
 
-pub fn error(line: u32, message: &str) {
-    panic!("[line {}] Error: {}", line, message);
-}
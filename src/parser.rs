@@ -1,106 +1,534 @@
-use crate::{expr::{Expr, self}, scanner::{TokenType, Token, Literal}};
-use crate::expr::BinaryOperator;
-use crate::expr::BinaryOperatorEnum;
-
-pub struct Parser {
-    pub start: usize,
-    pub current: usize,
-    pub tokens: Vec<Token>
+use crate::{error, report, warn, expr::{Expr, BinaryOperator, BinaryOperatorEnum, IncrementOperator, MatchArm, Pattern, UnaryOperator, UnaryOperatorEnum}, scanner::{TokenType, Token, Literal, ScanError}, interner::Symbol};
+
+// Binding power of infix operators, weakest first. A token that cannot
+// continue an expression has precedence None.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    None,
+    Comma,
+    Assignment,
+    Conditional,
+    Equality,
+    Comparison,
+    Range,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Term,
+    Factor,
+    Unary,
+    Exponent,
+    Call,
 }
 
-impl Parser {
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Self::None => Self::Comma,
+            Self::Comma => Self::Assignment,
+            Self::Assignment => Self::Conditional,
+            Self::Conditional => Self::Equality,
+            Self::Equality => Self::Comparison,
+            Self::Comparison => Self::Range,
+            Self::Range => Self::BitOr,
+            Self::BitOr => Self::BitXor,
+            Self::BitXor => Self::BitAnd,
+            Self::BitAnd => Self::Shift,
+            Self::Shift => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor => Self::Unary,
+            Self::Unary => Self::Exponent,
+            Self::Exponent | Self::Call => Self::Call,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+type PrefixFn<'src> = fn(&mut Parser<'src>) -> Result<Expr, ParserError>;
+type InfixFn<'src> = fn(&mut Parser<'src>, Expr) -> Result<Expr, ParserError>;
+
+struct ParseRule<'src> {
+    prefix: Option<PrefixFn<'src>>,
+    infix: Option<InfixFn<'src>>,
+    precedence: Precedence,
+    associativity: Associativity,
+}
+
+// Pulls tokens from a Lexer (or any other token source) one at a time,
+// keeping only the current and previous token.
+pub struct Parser<'src> {
+    tokens: Box<dyn Iterator<Item = Result<Token<'src>, ScanError>> + 'src>,
+    previous: Token<'src>,
+    current: Token<'src>,
+    // scan errors pulled from `tokens`; the bad lexemes are skipped
+    scan_errors: Vec<ScanError>,
+    // problems that do not stop parsing, such as unreachable match arms
+    warnings: Vec<ParserError>,
+    // set while parsing a match guard, whose `=>` ends it rather than
+    // turning a parenthesized expression into a lambda
+    in_guard: bool,
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(tokens: impl Iterator<Item = Result<Token<'src>, ScanError>> + 'src) -> Self {
+        let eof = Token { token_type: TokenType::Eof, lexme: "", literal: None, line: 0, leading_trivia: "", trailing_trivia: "" };
+        let mut parser = Parser { tokens: Box::new(tokens), previous: eof, current: eof, scan_errors: Vec::new(), warnings: Vec::new(), in_guard: false };
+        parser.current = parser.next_token();
+        parser
+    }
+
+    // Reports scan errors, warnings and syntax errors, and returns None if
+    // the input is not an expression.
+    pub fn parse(&mut self) -> Option<Expr> {
+        let result = self.try_parse();
+        for e in &self.scan_errors {
+            error(e.line.try_into().unwrap(), &e.message);
+        }
+        for w in &self.warnings {
+            w.warn();
+        }
+        match result {
+            Ok(expr) => Some(expr),
+            Err(e) => {
+                e.report();
+                None
+            }
+        }
+    }
 
-    pub fn parse(&mut self) -> Expr {
-        return self.expression().unwrap();
+    // Like `parse`, but leaves the errors to the caller instead of printing
+    // them. Scan errors and warnings are in `scan_errors` and `warnings`.
+    pub fn try_parse(&mut self) -> Result<Expr, ParserError> {
+        let expr = self.expression()?;
+        // `check` is always false at Eof, so compare the type directly
+        if self.peek().token_type != TokenType::Eof {
+            return Err(self.error(&self.current, "Expect end of expression."));
+        }
+        Ok(expr)
+    }
+
+    pub fn scan_errors(&self) -> &[ScanError] {
+        &self.scan_errors
+    }
+
+    pub fn warnings(&self) -> &[ParserError] {
+        &self.warnings
     }
 
     pub fn expression(&mut self) -> Result<Expr, ParserError> {
-        self.equality()
+        self.parse_precedence(Precedence::Comma)
     }
 
-    pub fn equality(&mut self) -> Result<Expr, ParserError> {
-        let mut expr: Expr = self.comparison().unwrap();
+    // The operator table. Adding an operator is one row: the parse function
+    // used when the token starts an expression, the one used when it follows
+    // an operand, and how tightly and in which direction the infix form binds.
+    fn rule(token_type: TokenType) -> ParseRule<'src> {
+        use Associativity::*;
+        use TokenType::*;
+
+        let (prefix, infix, precedence, associativity): (Option<PrefixFn<'src>>, Option<InfixFn<'src>>, Precedence, Associativity) = match token_type {
+            LeftParen          => (Some(Self::grouping),         Some(Self::call),              Precedence::Call,        Left),
+            LeftBrace          => (Some(Self::map),              None,                          Precedence::None,        Left),
+            LeftBracket        => (Some(Self::list),             Some(Self::index),             Precedence::Call,        Left),
+            Dot                => (None,                         Some(Self::dot),               Precedence::Call,        Left),
+            Identifier         => (Some(Self::variable),         None,                          Precedence::None,        Left),
+            Equal | PlusEqual
+            | MinusEqual
+            | StarEqual
+            | SlashEqual       => (None,                         Some(Self::assignment),        Precedence::Assignment,  Right),
+            PlusPlus
+            | MinusMinus       => (Some(Self::prefix_increment), Some(Self::postfix_increment), Precedence::Call,        Left),
+            Comma              => (None,                         Some(Self::binary),            Precedence::Comma,       Left),
+            Question           => (None,                         Some(Self::conditional),       Precedence::Conditional, Right),
+            Minus              => (Some(Self::unary),            Some(Self::binary),            Precedence::Term,        Left),
+            Plus               => (None,                         Some(Self::binary),            Precedence::Term,        Left),
+            Slash | Star
+            | Percent          => (None,                         Some(Self::binary),            Precedence::Factor,      Left),
+            StarStar           => (None,                         Some(Self::binary),            Precedence::Exponent,    Right),
+            DotDot
+            | DotDotEqual      => (None,                         Some(Self::binary),            Precedence::Range,       Left),
+            Pipe               => (None,                         Some(Self::binary),            Precedence::BitOr,       Left),
+            Caret              => (None,                         Some(Self::binary),            Precedence::BitXor,      Left),
+            Ampersand          => (None,                         Some(Self::binary),            Precedence::BitAnd,      Left),
+            LessLess
+            | GreaterGreater   => (None,                         Some(Self::binary),            Precedence::Shift,       Left),
+            Bang | Tilde       => (Some(Self::unary),            None,                          Precedence::None,        Left),
+            BangEqual
+            | EqualEqual       => (None,                         Some(Self::binary),            Precedence::Equality,    Left),
+            Greater
+            | GreaterEqual
+            | Less
+            | LessEqual        => (None,                         Some(Self::binary),            Precedence::Comparison,  Left),
+            Number | String
+            | True | False
+            | Nil              => (Some(Self::literal),          None,                          Precedence::None,        Left),
+            Match              => (Some(Self::match_expression), None,                          Precedence::None,        Left),
+            InterpolationStart => (Some(Self::interpolation),    None,                          Precedence::None,        Left),
+            _                  => (None,                         None,                          Precedence::None,        Left),
+        };
+
+        ParseRule { prefix, infix, precedence, associativity }
+    }
 
-        while self.search(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator: Token = self.previous();
-            let right: Expr = self.comparison().unwrap();
-            expr = Expr::Binary(Box::new(expr), Box::new(self.expr_from_tok(operator).unwrap()), Box::new(right));
+    // Parses an expression whose infix operators all bind at least as tightly
+    // as `precedence`.
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expr, ParserError> {
+        let token = self.peek();
+        let prefix = match Self::rule(token.token_type).prefix {
+            Some(prefix) => prefix,
+            None => return Err(self.error(&token, "Expect expression.")),
+        };
+        self.advance();
+        let mut expr = prefix(self)?;
+
+        loop {
+            let rule = Self::rule(self.peek().token_type);
+            match rule.infix {
+                Some(infix) if rule.precedence >= precedence => {
+                    self.advance();
+                    expr = infix(self, expr)?;
+                },
+                _ => return Ok(expr),
+            }
         }
+    }
 
-        Ok(expr)
+    fn binary(&mut self, left: Expr) -> Result<Expr, ParserError> {
+        let operator = self.previous();
+        let rule = Self::rule(operator.token_type);
+        let right_precedence = match rule.associativity {
+            Associativity::Left => rule.precedence.next(),
+            Associativity::Right => rule.precedence,
+        };
+        let right = self.parse_precedence(right_precedence)?;
+
+        Ok(Expr::Binary(Box::new(left), Box::new(Self::binary_operator(operator)), Box::new(right)))
     }
 
-    fn comparison(&mut self) -> Result<Expr, ParserError> {
-        let mut expr: Expr = self.term().unwrap();
+    // `condition ? then : else`. As in C, the then branch may be any
+    // expression since it is delimited by `:`.
+    fn conditional(&mut self, condition: Expr) -> Result<Expr, ParserError> {
+        let then_branch = self.delimited(Precedence::Comma)?;
+        self.consume(TokenType::Colon, "Expect ':' after then branch of conditional expression.")?;
+        let else_branch = self.parse_precedence(Precedence::Conditional)?;
+
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then_branch), Box::new(else_branch)))
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParserError> {
+        let operator = self.previous();
+        let right = self.parse_precedence(Precedence::Unary)?;
+
+        Ok(Expr::Unary(Box::new(Self::unary_operator(operator)), Box::new(right)))
+    }
 
-        while self.search(vec![TokenType::Greater, TokenType::GreaterEqual, TokenType::Less, TokenType::LessEqual]) {
-            let operator = self.previous();
-            let right = self.term();
-            expr = Expr::Binary(Box::new(expr), Box::new(self.expr_from_tok(operator).unwrap()), Box::new(right.unwrap()));
+    fn assignment(&mut self, target: Expr) -> Result<Expr, ParserError> {
+        let operator = self.previous();
+        self.check_target(&target, &operator)?;
+        let value = self.parse_precedence(Precedence::Assignment)?;
+
+        let operator = match operator.token_type {
+            TokenType::PlusEqual => Some(BinaryOperatorEnum::Plus),
+            TokenType::MinusEqual => Some(BinaryOperatorEnum::Minus),
+            TokenType::StarEqual => Some(BinaryOperatorEnum::Star),
+            TokenType::SlashEqual => Some(BinaryOperatorEnum::Slash),
+            _ => None,
+        };
+        Ok(Expr::Assign(Box::new(target), operator, Box::new(value)))
+    }
+
+    // The target is parsed at call level, so `++a ** 2` increments `a` and
+    // leaves `**` to the enclosing expression.
+    fn prefix_increment(&mut self) -> Result<Expr, ParserError> {
+        let operator = self.previous();
+        let target = self.parse_precedence(Precedence::Call)?;
+        self.check_target(&target, &operator)?;
+
+        let operator = if operator.token_type == TokenType::PlusPlus { IncrementOperator::PreIncrement } else { IncrementOperator::PreDecrement };
+        Ok(Expr::Increment(Box::new(target), operator))
+    }
+
+    fn postfix_increment(&mut self, target: Expr) -> Result<Expr, ParserError> {
+        let operator = self.previous();
+        self.check_target(&target, &operator)?;
+
+        let operator = if operator.token_type == TokenType::PlusPlus { IncrementOperator::PostIncrement } else { IncrementOperator::PostDecrement };
+        Ok(Expr::Increment(Box::new(target), operator))
+    }
+
+    fn check_target(&self, target: &Expr, operator: &Token<'src>) -> Result<(), ParserError> {
+        match target {
+            Expr::Variable(_) | Expr::Get(..) | Expr::Index(..) => Ok(()),
+            _ => Err(self.error(operator, "Invalid assignment target.")),
         }
+    }
 
-        Ok(expr)
+    fn dot(&mut self, object: Expr) -> Result<Expr, ParserError> {
+        let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+        Ok(Expr::Get(Box::new(object), Symbol::intern(name.lexme)))
     }
 
-    fn term(&mut self) -> Result<Expr, ParserError> {
-        let mut expr = self.factor();
+    // Arguments are parsed above the comma operator so that commas separate them.
+    fn call(&mut self, callee: Expr) -> Result<Expr, ParserError> {
+        let mut arguments = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                arguments.push(self.delimited(Precedence::Assignment)?);
+                if !self.search(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call(Box::new(callee), arguments))
+    }
 
-        while self.search(vec![TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous();
-            let right = self.factor();
-            expr = Ok(Expr::Binary(Box::new(expr.unwrap()), Box::new(self.expr_from_tok(operator).unwrap()), Box::new(right.unwrap())));
+    fn list(&mut self) -> Result<Expr, ParserError> {
+        let mut elements = Vec::new();
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.delimited(Precedence::Assignment)?);
+                if !self.search(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
         }
-        expr
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+
+        Ok(Expr::List(elements))
     }
 
-    fn factor(&mut self) -> Result<Expr, ParserError> {
-        let mut expr = self.unary();
+    // There are no blocks in an expression, so a `{` there always starts a map.
+    fn map(&mut self) -> Result<Expr, ParserError> {
+        let mut entries = Vec::new();
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.delimited(Precedence::Assignment)?;
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                let value = self.delimited(Precedence::Assignment)?;
+                entries.push((key, value));
+                if !self.search(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+
+        Ok(Expr::Map(entries))
+    }
 
-        while self.search(vec![TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous();
-            let right = self.unary();
-            expr = Ok(Expr::Binary(Box::new(expr.unwrap()), Box::new(self.expr_from_tok(operator).unwrap()), Box::new(right.unwrap())));
+    // `object[index]`, or a slice when the brackets contain a `:`.
+    fn index(&mut self, object: Expr) -> Result<Expr, ParserError> {
+        let start = if self.check(TokenType::Colon) { None } else { Some(self.delimited(Precedence::Comma)?) };
+        if !self.search(vec![TokenType::Colon]) {
+            self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+            return Ok(Expr::Index(Box::new(object), Box::new(start.unwrap())));
         }
-        expr
+
+        let end = if self.check(TokenType::RightBracket) { None } else { Some(self.delimited(Precedence::Comma)?) };
+        self.consume(TokenType::RightBracket, "Expect ']' after slice.")?;
+        Ok(Expr::Slice(Box::new(object), start.map(Box::new), end.map(Box::new)))
     }
 
-    fn unary(&mut self) -> Result<Expr, ParserError> {
-        if self.search(vec![TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
-            let right = self.unary().unwrap();
-            self.expr_from_tok(operator.clone()).unwrap();
-            return Ok(Expr::Unary(Box::new(self.expr_from_tok(operator).unwrap()), Box::new(right)));
+    fn variable(&mut self) -> Result<Expr, ParserError> {
+        Ok(Expr::Variable(Symbol::intern(self.previous().lexme)))
+    }
+
+    // A parenthesized expression, or the parameter list of a lambda when
+    // followed by `=>`. The parameters are parsed as an expression first and
+    // then checked, since `(a, b)` is only known to be a list at the arrow.
+    // Directly inside a match guard the `=>` belongs to the arm instead.
+    fn grouping(&mut self) -> Result<Expr, ParserError> {
+        if !self.in_guard && self.search(vec![TokenType::RightParen]) {
+            self.consume(TokenType::EqualGreater, "Expect '=>' after '()'.")?;
+            return self.lambda(Vec::new());
         }
 
-        self.primary()
+        let expr = self.delimited(Precedence::Comma)?;
+        self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+        if !self.in_guard && self.search(vec![TokenType::EqualGreater]) {
+            let mut params = Vec::new();
+            self.parameters(expr, &mut params)?;
+            return self.lambda(params);
+        }
+        Ok(Expr::Grouping(Box::new(expr)))
     }
 
-    fn primary(&mut self) -> Result<Expr, ParserError> {
-        if self.search(vec![TokenType::False]) {
-            return Ok(Expr::Literal(Literal::False));
+    fn parameters(&self, expr: Expr, params: &mut Vec<Symbol>) -> Result<(), ParserError> {
+        match expr {
+            Expr::Variable(name) if params.contains(&name) => Err(self.error(&self.previous, "Duplicate parameter name.")),
+            Expr::Variable(name) => {
+                params.push(name);
+                Ok(())
+            },
+            Expr::Binary(left, operator, right) if matches!(*operator, Expr::BinaryOp(BinaryOperator { token_type: BinaryOperatorEnum::Comma, .. })) => {
+                self.parameters(*left, params)?;
+                self.parameters(*right, params)
+            },
+            _ => Err(self.error(&self.previous, "Expect parameter name.")),
         }
-        if self.search(vec![TokenType::True]) {
-            return Ok(Expr::Literal(Literal::True));
+    }
+
+    // The body is parsed above the comma operator so that a lambda can be
+    // passed as an argument.
+    fn lambda(&mut self, params: Vec<Symbol>) -> Result<Expr, ParserError> {
+        let body = self.parse_precedence(Precedence::Assignment)?;
+        Ok(Expr::Lambda(params, Box::new(body)))
+    }
+
+    fn literal(&mut self) -> Result<Expr, ParserError> {
+        Ok(Expr::Literal(Self::literal_value(self.previous())))
+    }
+
+    fn literal_value(token: Token<'src>) -> Literal {
+        match token.token_type {
+            TokenType::False => Literal::False,
+            TokenType::True => Literal::True,
+            TokenType::Nil => Literal::Nil,
+            _ => token.literal.unwrap(),
+        }
+    }
+
+    // Arms are separated by commas, with an optional trailing one. An arm is
+    // unreachable after an unguarded `_` or binding, or when an earlier
+    // unguarded arm has the same literal; both are reported as warnings.
+    fn match_expression(&mut self) -> Result<Expr, ParserError> {
+        let value = self.delimited(Precedence::Comma)?;
+        self.consume(TokenType::LeftBrace, "Expect '{' after match value.")?;
+
+        let mut arms = Vec::new();
+        let mut exhausted = false;
+        let mut seen = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let start = self.peek();
+            let pattern = self.pattern()?;
+            let guard = if self.search(vec![TokenType::If]) { Some(self.guard()?) } else { None };
+            self.consume(TokenType::EqualGreater, "Expect '=>' after match pattern.")?;
+            let body = self.delimited(Precedence::Assignment)?;
+
+            let duplicate = matches!(&pattern, Pattern::Literal(literal) if seen.contains(literal));
+            if exhausted || duplicate {
+                self.warnings.push(self.error(&start, "Unreachable match arm."));
+            }
+            if guard.is_none() {
+                exhausted |= pattern.is_irrefutable();
+                if let Pattern::Literal(literal) = pattern {
+                    seen.push(literal);
+                }
+            }
+            arms.push(MatchArm { pattern, guard, body });
+
+            if !self.search(vec![TokenType::Comma]) {
+                break;
+            }
         }
-        if self.search(vec![TokenType::Nil]) {
-            return Ok(Expr::Literal(Literal::Nil));
+        self.consume(TokenType::RightBrace, "Expect '}' after match arms.")?;
+
+        Ok(Expr::Match(Box::new(value), arms))
+    }
+
+    // A guard ends at the arm's `=>`, so `(x)` in it is never a lambda's
+    // parameter list.
+    fn guard(&mut self) -> Result<Expr, ParserError> {
+        let in_guard = std::mem::replace(&mut self.in_guard, true);
+        let guard = self.parse_precedence(Precedence::Assignment);
+        self.in_guard = in_guard;
+        guard
+    }
+
+    // Parses an expression that is closed off by a bracket, `:` or `{`, so
+    // that a guard's `=>` cannot follow it and lambdas are allowed again.
+    fn delimited(&mut self, precedence: Precedence) -> Result<Expr, ParserError> {
+        let in_guard = std::mem::replace(&mut self.in_guard, false);
+        let expr = self.parse_precedence(precedence);
+        self.in_guard = in_guard;
+        expr
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, ParserError> {
+        if self.search(vec![TokenType::Identifier]) {
+            let name = Symbol::intern(self.previous().lexme);
+            if name.as_str() == "_" {
+                return Ok(Pattern::Wildcard);
+            }
+            if !self.search(vec![TokenType::LeftBrace]) {
+                return Ok(Pattern::Binding(name));
+            }
+
+            let mut fields = Vec::new();
+            if !self.check(TokenType::RightBrace) {
+                loop {
+                    let field = Symbol::intern(self.consume(TokenType::Identifier, "Expect field name in pattern.")?.lexme);
+                    let pattern = if self.search(vec![TokenType::Colon]) { self.pattern()? } else { Pattern::Binding(field) };
+                    fields.push((field, pattern));
+                    if !self.search(vec![TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBrace, "Expect '}' after field patterns.")?;
+            return Ok(Pattern::Instance(name, fields));
         }
-        if self.search(vec![TokenType::Number, TokenType::String]) {
-            return Ok(Expr::Literal(self.previous().literal.unwrap()));
+
+        if self.search(vec![TokenType::LeftBracket]) {
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.pattern()?);
+                    if !self.search(vec![TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBracket, "Expect ']' after list pattern.")?;
+            return Ok(Pattern::List(elements));
         }
-        if self.search(vec![TokenType::LeftParen]) {
-            let expr = self.expression();
-            self.consume(TokenType::RightParen, "Expect ')' after expression.".to_string()).unwrap();
-            return Ok(Expr::Grouping(Box::new(expr.unwrap())));
+
+        let start = self.pattern_literal()?;
+        if self.search(vec![TokenType::DotDot, TokenType::DotDotEqual]) {
+            let inclusive = self.previous().token_type == TokenType::DotDotEqual;
+            let end = self.pattern_literal()?;
+            return Ok(Pattern::Range(start, end, inclusive));
         }
+        Ok(Pattern::Literal(start))
+    }
 
-        Err(ParserError {
-            line: None,
-            lexme: None,
-            message: "Expected expression.".to_string(),  
-        })
+    // A literal, or a negated number literal.
+    fn pattern_literal(&mut self) -> Result<Literal, ParserError> {
+        if self.search(vec![TokenType::Minus]) {
+            let token = self.consume(TokenType::Number, "Expect number after '-' in pattern.")?;
+            return match Self::literal_value(token) {
+                Literal::Num(n) => Ok(Literal::Num(-n)),
+                _ => unreachable!("number token without a value"),
+            };
+        }
+        if self.search(vec![TokenType::Number, TokenType::String, TokenType::True, TokenType::False, TokenType::Nil]) {
+            return Ok(Self::literal_value(self.previous()));
+        }
+        Err(self.error(&self.current, "Expect pattern."))
+    }
 
-        // Err(self.error(}, None, "Primary Expression Error"))
+    // Collects the literal segments and embedded expressions of an
+    // interpolated string, starting just after its InterpolationStart token.
+    fn interpolation(&mut self) -> Result<Expr, ParserError> {
+        let mut parts = vec![Expr::Literal(self.previous().literal.unwrap())];
+        loop {
+            parts.push(self.delimited(Precedence::Comma)?);
+            if self.search(vec![TokenType::InterpolationSegment]) {
+                parts.push(Expr::Literal(self.previous().literal.unwrap()));
+                continue;
+            }
+            self.consume(TokenType::InterpolationEnd, "Expect '}' after interpolated expression.")?;
+            parts.push(Expr::Literal(self.previous().literal.unwrap()));
+            return Ok(Expr::Interpolation(parts));
+        }
     }
 
     fn search(&mut self, vec: Vec<TokenType>) -> bool {
@@ -113,8 +541,8 @@ impl Parser {
         false
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    fn previous(&self) -> Token<'src> {
+        self.previous
     }
 
     fn check(&self, toktype: TokenType) -> bool {
@@ -125,147 +553,245 @@ impl Parser {
         peek == toktype
     }
 
-    fn advance(&mut self) -> Token{
-        if !self.is_at_end() { self.current += 1; }
+    fn advance(&mut self) -> Token<'src> {
+        if !self.is_at_end() {
+            self.previous = self.current;
+            self.current = self.next_token();
+        }
         self.previous()
     }
 
+    // Scan errors are collected as they are pulled and the bad lexeme skipped.
+    // Doc comments belong to declarations, which the expression grammar does
+    // not have yet, so they are skipped too.
+    fn next_token(&mut self) -> Token<'src> {
+        loop {
+            match self.tokens.next() {
+                Some(Ok(token)) if token.token_type == TokenType::DocComment => (),
+                Some(Ok(token)) => return token,
+                Some(Err(e)) => self.scan_errors.push(e),
+                None => return self.current,
+            }
+        }
+    }
+
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
 
-    fn peek(&self) -> Token {
-        let tok = self.tokens[self.current].clone();
-        tok.clone()
+    fn peek(&self) -> Token<'src> {
+        self.current
     }
 
-    fn consume(&mut self, tok: TokenType, err: String) -> Result<Token, ParserError>{
-        dbg!("TOK: {}", tok);
+    fn consume(&mut self, tok: TokenType, err: &str) -> Result<Token<'src>, ParserError>{
         if self.check(tok) {
             return Ok(self.advance());
         }
-        Err(self.error(self.peek(), err))
+        Err(self.error(&self.current, err))
     }
 
-    fn synchronize(&mut self) {
-        self.advance();
+    fn binary_operator(operator: Token<'src>) -> Expr {
+        let token_type = match operator.token_type {
+            TokenType::EqualEqual => BinaryOperatorEnum::EqualEqual,
+            TokenType::BangEqual => BinaryOperatorEnum::NotEqual,
+            TokenType::Less => BinaryOperatorEnum::Less,
+            TokenType::LessEqual => BinaryOperatorEnum::LessEqual,
+            TokenType::Greater => BinaryOperatorEnum::Greater,
+            TokenType::GreaterEqual => BinaryOperatorEnum::GreaterEqual,
+            TokenType::Plus => BinaryOperatorEnum::Plus,
+            TokenType::Minus => BinaryOperatorEnum::Minus,
+            TokenType::Star => BinaryOperatorEnum::Star,
+            TokenType::Slash => BinaryOperatorEnum::Slash,
+            TokenType::Percent => BinaryOperatorEnum::Percent,
+            TokenType::StarStar => BinaryOperatorEnum::StarStar,
+            TokenType::Ampersand => BinaryOperatorEnum::Ampersand,
+            TokenType::Pipe => BinaryOperatorEnum::Pipe,
+            TokenType::Caret => BinaryOperatorEnum::Caret,
+            TokenType::LessLess => BinaryOperatorEnum::LessLess,
+            TokenType::GreaterGreater => BinaryOperatorEnum::GreaterGreater,
+            TokenType::DotDot => BinaryOperatorEnum::DotDot,
+            TokenType::DotDotEqual => BinaryOperatorEnum::DotDotEqual,
+            TokenType::Comma => BinaryOperatorEnum::Comma,
+            t => unreachable!("{} has no binary rule", t),
+        };
+
+        Expr::BinaryOp(BinaryOperator {
+            token_type,
+            lexme: Symbol::intern(operator.lexme),
+            literal: operator.literal,
+            line: operator.line
+        })
+    }
 
-        while !self.is_at_end() {
-            if self.previous().token_type == TokenType::Semicolon {
-                return;
-            }
+    fn unary_operator(operator: Token<'src>) -> Expr {
+        let token_type = match operator.token_type {
+            TokenType::Minus => UnaryOperatorEnum::Minus,
+            TokenType::Bang => UnaryOperatorEnum::Bang,
+            TokenType::Tilde => UnaryOperatorEnum::Tilde,
+            t => unreachable!("{} has no unary rule", t),
+        };
+
+        Expr::UnaryOp(UnaryOperator {
+            token_type,
+            lexme: Symbol::intern(operator.lexme),
+            literal: operator.literal,
+            line: operator.line
+        })
+    }
 
-            match self.peek().token_type {
-                TokenType::Class => return,
-                TokenType::Fun => return,
-                TokenType::Var => return,
-                TokenType::For => return,
-                TokenType::If => return,
-                TokenType::While => return,
-                TokenType::Print => return,
-                TokenType::Return => return,
-                _ => continue,
-            }
+    fn error(&self, tok: &Token<'src>, err: &str) -> ParserError{
+        let lexme = if tok.token_type == TokenType::Eof { None } else { Some(tok.lexme.to_string()) };
+        ParserError { line: tok.line, lexme, message: err.to_string() }
+    }
+}
+
+// A syntax error, or a warning from `Parser::warnings`. `lexme` is None when
+// it is at the end of the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParserError {
+    pub line: usize,
+    pub lexme: Option<String>,
+    pub message: String,
+}
+
+impl ParserError {
+    pub fn report(&self) {
+        report(self.line.try_into().unwrap(), &self.location(), &self.message);
+    }
+
+    pub fn warn(&self) {
+        warn(self.line.try_into().unwrap(), &self.location(), &self.message);
+    }
+
+    fn location(&self) -> String {
+        match &self.lexme {
+            Some(lexme) => format!(" at '{}'", lexme),
+            None => " at end".to_string(),
         }
+    }
+}
 
-        self.advance();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Lexer;
+
+    // The printed tree, or the error as `report` would locate it.
+    fn parse(source: &str) -> Result<String, String> {
+        Parser::new(Lexer::new(source)).try_parse().map(Expr::visit).map_err(|e| match e.lexme {
+            Some(lexme) => format!("at '{}': {}", lexme, e.message),
+            None => format!("at end: {}", e.message),
+        })
     }
 
-    fn expr_from_tok(&self, operator: Token) -> Result<Expr, &'static str> {
-        match operator.token_type {
-            TokenType::EqualEqual => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: BinaryOperatorEnum::EqualEqual,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-            
-            TokenType::BangEqual => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: BinaryOperatorEnum::NotEqual,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Less => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: BinaryOperatorEnum::Less,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::LessEqual => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::LessEqual,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Greater => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::GreaterEqual,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::GreaterEqual => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::GreaterEqual,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Plus => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::Plus,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Minus => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::Minus,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Star => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::Star,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Slash => Ok(Expr::BinaryOp(BinaryOperator{
-                token_type: crate::expr::BinaryOperatorEnum::Slash,
-                lexme: operator.lexme,
-                literal: operator.literal,
-                line: operator.line
-            })),
-
-            TokenType::Bang => Ok(Expr::UnaryOp(expr::UnaryOperator { 
-                token_type: crate::expr::UnaryOperatorEnum::Bang, 
-                lexme: operator.lexme, 
-                literal: operator.literal, 
-                line: operator.line
-            })),
-            _ => {
-                Err("ERROR")
-            }
+    fn check(cases: &[(&str, &str)]) {
+        for (source, expected) in cases {
+            assert_eq!(parse(source), Ok(expected.to_string()), "{}", source);
         }
     }
 
-    fn error(&self, tok: Token, err: String) -> ParserError{
-        if tok.token_type == TokenType::Eof {
-            return ParserError{line: None, lexme: None, message: err};
+    fn check_errors(cases: &[(&str, &str)]) {
+        for (source, expected) in cases {
+            assert_eq!(parse(source), Err(expected.to_string()), "{}", source);
         }
-        return ParserError{line: Some(tok.line), lexme: Some(tok.lexme), message: err};
     }
-}
 
-#[derive(Debug)]
-pub struct ParserError {
-    line: Option<usize>,
-    lexme: Option<String>,
-    message: String,
-}
\ No newline at end of file
+    #[test]
+    fn precedence_and_associativity() {
+        check(&[
+            ("1 + 2 * 3 - 4", "(Binary Operation(Binary Operation1+(Binary Operation2*3))-4)"),
+            ("(1 + 2) * 3", "(Binary Operation(Grouping Operation(Binary Operation1+2))*3)"),
+            ("2 ** 3 ** 2", "(Binary Operation2**(Binary Operation3**2))"),
+            ("-2 ** 2", "(Unary Operation-(Binary Operation2**2))"),
+            ("a ? b : c ? d : e", "(Conditional Operationab(Conditional Operationcde))"),
+            ("a, b = c", "(Binary Operationa,(Assignment=bc))"),
+            ("x = y += 1", "(Assignment=x(Assignment+=y1))"),
+            ("~a & b | c", "(Binary Operation(Binary Operation(Unary Operation~a)&b)|c)"),
+            ("a ^ b & c", "(Binary Operationa^(Binary Operationb&c))"),
+            ("1 << 2 + 3", "(Binary Operation1<<(Binary Operation2+3))"),
+            ("a == b < c", "(Binary Operationa==(Binary Operationb<c))"),
+            ("0..n - 1", "(Binary Operation0..(Binary Operationn-1))"),
+            ("0..=9 < n", "(Binary Operation(Binary Operation0..=9)<n)"),
+        ]);
+    }
+
+    #[test]
+    fn postfix_and_primary_forms() {
+        check(&[
+            ("a.b(c)[d]++", "(Increment_++(Index(Call(Property Accessa.b)c)d))"),
+            ("++a ** 2", "(Binary Operation(Increment++_a)**2)"),
+            ("(a, b) => a + b", "(Lambda(a,b)(Binary Operationa+b))"),
+            ("f((x) => x, 1)", "(Callf(Lambda(x)x)1)"),
+            ("[1, {\"k\": 2}][0:]", "(Slice(List1(Mapk:2))[0:])"),
+            ("\"a${b}c\"", "(Interpolationabc)"),
+        ]);
+    }
+
+    fn warnings(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.try_parse().unwrap();
+        parser.warnings().iter().map(|w| format!("{}:{}", w.lexme.as_deref().unwrap(), w.message)).collect()
+    }
+
+    #[test]
+    fn match_patterns() {
+        check(&[
+            ("match x { 0 => a, -1.5 => b, \"s\" => c, true => d, nil => e }", "(Matchx[0 => a][-1.5 => b][s => c][true => d][nil => e])"),
+            ("match x { 1..10 => a, -5..=-1 => b, \"a\"..\"z\" => c }", "(Matchx[1..10 => a][-5..=-1 => b][a..z => c])"),
+            ("match x { _ => a }", "(Matchx[_ => a])"),
+            ("match x { n => n }", "(Matchx[n => n])"),
+            ("match x { [] => a, [h, _, [1, t]] => h }", "(Matchx[[] => a][[h, _, [1, t]] => h])"),
+            ("match x { Point { x, y: 0, z: [a] } => x, Empty {} => 0 }", "(Matchx[Point { x: x, y: 0, z: [a] } => x][Empty {} => 0])"),
+            ("match x { n if n > 0 => n, _ => 0, }", "(Matchx[n if (Binary Operationn>0) => n][_ => 0])"),
+            ("match x {}", "(Matchx)"),
+            ("match f(a) { 1 => 2 } + 1", "(Binary Operation(Match(Callfa)[1 => 2])+1)"),
+        ]);
+        check_errors(&[
+            ("match x { + => 1 }", "at '+': Expect pattern."),
+            ("match x { - a => 1 }", "at 'a': Expect number after '-' in pattern."),
+            ("match x { 1.. => 1 }", "at '=>': Expect pattern."),
+            ("match x { Point { 1 } => 1 }", "at '1': Expect field name in pattern."),
+            ("match x { 1 2 }", "at '2': Expect '=>' after match pattern."),
+            ("match x { 1 => 2", "at end: Expect '}' after match arms."),
+            ("match x 1", "at '1': Expect '{' after match value."),
+        ]);
+    }
+
+    // Arms after an unguarded `_` or binding, and repeats of an unguarded
+    // literal, can never run. Guarded arms make nothing unreachable.
+    #[test]
+    fn unreachable_match_arms() {
+        assert_eq!(warnings("match x { 1 => a, 1 => b, 1 if c => d, _ => e, 2 => f, n => g }"), vec![
+            "1:Unreachable match arm.",
+            "1:Unreachable match arm.",
+            "2:Unreachable match arm.",
+            "n:Unreachable match arm.",
+        ]);
+        assert!(warnings("match x { 1 if c => a, 1 => b, n if d => c, 2 => d }").is_empty());
+        assert!(warnings("match x { [a] => a, Point { x } => x, [b] => b }").is_empty());
+    }
+
+    // The arm's `=>` must not turn a parenthesized guard into a lambda.
+    #[test]
+    fn parenthesized_match_guards() {
+        check(&[
+            ("match v { x if (x > 0) => 1, _ => 2 }", "(Matchv[x if (Grouping Operation(Binary Operationx>0)) => 1][_ => 2])"),
+            ("match v { x if (x) => 1 }", "(Matchv[x if (Grouping Operationx) => 1])"),
+            ("match v { x if f((y) => y) => (a) => a }", "(Matchv[x if (Callf(Lambda(y)y)) => (Lambda(a)a)])"),
+        ]);
+    }
+
+    #[test]
+    fn error_positions() {
+        check_errors(&[
+            ("1 +", "at end: Expect expression."),
+            (")", "at ')': Expect expression."),
+            ("1 + * 2", "at '*': Expect expression."),
+            ("(1", "at end: Expect ')' after expression."),
+            ("a + b = c", "at '=': Invalid assignment target."),
+            ("1 2", "at '2': Expect end of expression."),
+            ("1 )", "at ')': Expect end of expression."),
+            ("a b = c", "at 'b': Expect end of expression."),
+        ]);
+    }
+}
@@ -1,23 +1,63 @@
-use std::{fmt::{Debug, Display, self}, collections::HashMap};
+use std::{fmt::{Debug, Display, self}, collections::HashMap, borrow::Cow};
 
-use crate::error;
+use lazy_static::lazy_static;
+use unicode_xid::UnicodeXID;
+
+use crate::interner::Symbol;
+
+lazy_static! {
+    static ref KEYWORDS: HashMap<&'static str, TokenType> = {
+        let mut m = HashMap::new();
+        m.insert("and", TokenType::And);
+        m.insert("break", TokenType::Break);
+        m.insert("class", TokenType::Class);
+        m.insert("continue", TokenType::Continue);
+        m.insert("else", TokenType::Else);
+        m.insert("false", TokenType::False);
+        m.insert("for", TokenType::For);
+        m.insert("fun", TokenType::Fun);
+        m.insert("if", TokenType::If);
+        m.insert("in", TokenType::In);
+        m.insert("match", TokenType::Match);
+        m.insert("nil", TokenType::Nil);
+        m.insert("or", TokenType::Or);
+        m.insert("print", TokenType::Print);
+        m.insert("return", TokenType::Return);
+        m.insert("super", TokenType::Super);
+        m.insert("this", TokenType::This);
+        m.insert("true", TokenType::True);
+        m.insert("var", TokenType::Var);
+        m.insert("while", TokenType::While);
+        m
+    };
+}
 
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum TokenType {
     // single character tokens
-    LeftParen, RightParen, LeftBrace, RightBrace, 
+    LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
     Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+    Question, Colon, Percent, Ampersand, Pipe, Caret, Tilde,
 
     // one or two character tokens
-    Bang, BangEqual, Equal, EqualEqual, 
+    Bang, BangEqual, Equal, EqualEqual, EqualGreater,
     Greater, GreaterEqual, Less, LessEqual,
+    StarStar, LessLess, GreaterGreater, DotDot, DotDotEqual,
+    PlusEqual, MinusEqual, StarEqual, SlashEqual, PlusPlus, MinusMinus,
 
     // literals
     Identifier, String, Number,
 
+    // string interpolation: "a${x}b${y}c" scans as
+    // InterpolationStart("a") x InterpolationSegment("b") y InterpolationEnd("c")
+    InterpolationStart, InterpolationSegment, InterpolationEnd,
+
+    // `/// text` and `/** text */`, with the text as a Str literal
+    DocComment,
+
     // keywords
-    And, Class, Else, False, Fun, For, If, Nil, Or,
+    And, Break, Class, Continue, Else, False, Fun, For, If, In, Match, Nil, Or,
     Print, Return, Super, This, True, Var, While,
 
     Eof
@@ -29,9 +69,9 @@ impl fmt::Display for TokenType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Literal {
-    Str(String),
+    Str(Symbol),
     Num(f64),
     False,
     True,
@@ -43,225 +83,614 @@ impl Display for Literal {
         match self {
             Self::Str(s) => write!(f, "{}", s),
             Self::Num(n) => write!(f, "{}", n),
-            s => write!(f, "{}", s)
+            Self::False => write!(f, "false"),
+            Self::True => write!(f, "true"),
+            Self::Nil => write!(f, "nil"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Token {
+// Lexemes borrow from the source, so tokens are cheap to copy around.
+//
+// Trivia is only filled in by `Lexer::with_trivia`. Trailing trivia is the
+// spaces and `//` comment after the token up to the end of its line; all
+// other whitespace, newlines and comments lead the following token.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Token<'src> {
     pub token_type: TokenType,
-    pub lexme: String,
+    pub lexme: &'src str,
     pub literal: Option<Literal>,
     pub line: usize,
+    pub leading_trivia: &'src str,
+    pub trailing_trivia: &'src str,
 }
 
-impl Token {
-    pub fn to_string(&self) -> String {
-        let s = 
-        "tok: ".to_string() + &self.token_type.to_string() + 
-        " lex: " + &self.lexme + 
-        " line: " + &self.line.to_string();
-        s.to_string()
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tok: {} lex: {} line: {}", self.token_type, self.lexme, self.line)
     }
 }
 
-pub struct Scanner {
-    source: String,
-    source_chars: Vec<char>,
-    pub tokens: Vec<Token>,
-    start: usize,
-    pub current: usize,
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
     pub line: usize,
-    map: HashMap<String, TokenType>
+    pub message: String,
 }
 
-impl Default for Scanner {
-    fn default() -> Self {
-        let mut m = HashMap::new();
-        m.insert("and".to_string(), TokenType::And);
-        m.insert("class".to_string(), TokenType::Class);
-        m.insert("else".to_string(), TokenType::Else);
-        m.insert("false".to_string(), TokenType::False);
-        m.insert("for".to_string(), TokenType::For);
-        m.insert("fun".to_string(), TokenType::Fun);
-        m.insert("if".to_string(), TokenType::If);
-        m.insert("nil".to_string(), TokenType::Nil);
-        m.insert("or".to_string(), TokenType::Or);
-        m.insert("print".to_string(), TokenType::Print);
-        m.insert("return".to_string(), TokenType::Return);
-        m.insert("super".to_string(), TokenType::Super);
-        m.insert("this".to_string(), TokenType::This);
-        m.insert("true".to_string(), TokenType::True);
-        m.insert("var".to_string(), TokenType::Var);
-        m.insert("while".to_string(), TokenType::While);
-
-        Self { 
-            source: Default::default(), 
-            source_chars: Default::default(), 
-            tokens: Default::default(), 
-            start: Default::default(), 
-            current: Default::default(), 
-            line: Default::default(), 
-            map: m 
+// Scans tokens on demand. Offsets are byte positions into `source`, always
+// on char boundaries, so lexemes are plain slices of the source.
+pub struct Lexer<'src> {
+    source: &'src str,
+    start: usize,
+    current: usize,
+    line: usize,
+    // brace depth inside each `${ ... }` currently being scanned
+    interpolations: Vec<usize>,
+    done: bool,
+    trivia: bool,
+    trivia_start: usize,
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, ScanError>;
+
+    // Yields every token up to and including Eof, then None.
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.start = self.current;
+
+            if self.is_at_end() {
+                if !self.interpolations.is_empty() {
+                    self.interpolations.clear();
+                    return Some(Err(self.error("Unterminated string interpolation.")));
+                }
+                self.done = true;
+                let eof = self.token(TokenType::Eof, None);
+                return Some(Ok(self.attach_trivia(eof)));
+            }
+
+            if let Some(result) = self.scan_token() {
+                return Some(result.map(|token| self.attach_trivia(token)));
+            }
         }
+        None
     }
 }
 
-impl Scanner {
-    pub fn scan_tokens(&mut self, source: String) -> &Vec<Token> {
-        self.source = source.clone();
-        self.source_chars = source.as_str().chars().collect();
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+impl<'src> Lexer<'src> {
+    pub fn new(source: &'src str) -> Self {
+        Self {
+            source,
+            start: 0,
+            current: 0,
+            line: 1,
+            interpolations: Vec::new(),
+            done: false,
+            trivia: false,
+            trivia_start: 0,
         }
+    }
 
-        let eof = Token {
-            token_type: TokenType::Eof,
-            lexme: "".to_string(),
-            literal: None,
-            line: self.line
-        };
+    // Continues scanning `source` from byte `offset`, which must be a point
+    // where an earlier lexer was between tokens and outside any `${ ... }`.
+    pub fn resume(source: &'src str, offset: usize, line: usize) -> Self {
+        Self { start: offset, current: offset, line, ..Self::new(source) }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.current
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
 
-        self.tokens.push(eof);
-        &self.tokens
+    // True when not inside an interpolated expression, i.e. when `resume`
+    // could restart scanning here.
+    pub fn is_top_level(&self) -> bool {
+        self.interpolations.is_empty()
     }
 
-    fn is_at_end(&self) -> bool{
+    // Lossless mode: concatenating each token's leading trivia, lexeme and
+    // trailing trivia reproduces the source byte for byte. Text skipped by a
+    // ScanError becomes part of the next token's leading trivia.
+    pub fn with_trivia(source: &'src str) -> Self {
+        Self { trivia: true, ..Self::new(source) }
+    }
+
+    fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    fn scan_token(&mut self) {
+    // Scans the lexeme at `start`. Whitespace and comments yield None.
+    fn scan_token(&mut self) -> Option<Result<Token<'src>, ScanError>> {
         let c = self.advance();
-        self.match_toks(c);
-    }
-
-    pub fn match_toks(&mut self, c: char) {
-        match c {
-            '(' => self.add_token2(TokenType::LeftParen),
-            ')' => self.add_token2(TokenType::RightParen),
-            '{' => self.add_token2(TokenType::LeftBrace),
-            '}' => self.add_token2(TokenType::RightBrace),
-            ',' => self.add_token2(TokenType::Comma),
-            '.' => self.add_token2(TokenType::Dot),
-            '-' => self.add_token2(TokenType::Minus),
-            '+' => self.add_token2(TokenType::Plus),
-            '*' => self.add_token2(TokenType::Star),
-            ';' => self.add_token2(TokenType::Semicolon),
-            '!' => if self.matched('=') {self.add_token2(TokenType::BangEqual)} else {self.add_token2(TokenType::Bang)},
-            '=' => if self.matched('=') {self.add_token2(TokenType::EqualEqual)} else {self.add_token2(TokenType::Equal)},
-            '<' => if self.matched('=') {self.add_token2(TokenType::LessEqual)} else {self.add_token2(TokenType::Less)},
-            '>' => if self.matched('=') {self.add_token2(TokenType::GreaterEqual)} else {self.add_token2(TokenType::Greater)},
-            '/' => { // todo(CHECK why unreachable pattern)
+        let token_type = match c {
+            '(' => TokenType::LeftParen,
+            ')' => TokenType::RightParen,
+            '[' => TokenType::LeftBracket,
+            ']' => TokenType::RightBracket,
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                TokenType::LeftBrace
+            },
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    return Some(self.string_segment(false));
+                },
+                Some(depth) => {
+                    *depth -= 1;
+                    TokenType::RightBrace
+                },
+                None => TokenType::RightBrace,
+            },
+            ',' => TokenType::Comma,
+            '.' => if !self.matched('.') {TokenType::Dot} else if self.matched('=') {TokenType::DotDotEqual} else {TokenType::DotDot},
+            '-' => if self.matched('-') {TokenType::MinusMinus} else if self.matched('=') {TokenType::MinusEqual} else {TokenType::Minus},
+            '+' => if self.matched('+') {TokenType::PlusPlus} else if self.matched('=') {TokenType::PlusEqual} else {TokenType::Plus},
+            '*' => if self.matched('*') {TokenType::StarStar} else if self.matched('=') {TokenType::StarEqual} else {TokenType::Star},
+            ';' => TokenType::Semicolon,
+            '?' => TokenType::Question,
+            ':' => TokenType::Colon,
+            '%' => TokenType::Percent,
+            '&' => TokenType::Ampersand,
+            '|' => TokenType::Pipe,
+            '^' => TokenType::Caret,
+            '~' => TokenType::Tilde,
+            '!' => if self.matched('=') {TokenType::BangEqual} else {TokenType::Bang},
+            '=' => if self.matched('=') {TokenType::EqualEqual} else if self.matched('>') {TokenType::EqualGreater} else {TokenType::Equal},
+            '<' => if self.matched('=') {TokenType::LessEqual} else if self.matched('<') {TokenType::LessLess} else {TokenType::Less},
+            '>' => if self.matched('=') {TokenType::GreaterEqual} else if self.matched('>') {TokenType::GreaterGreater} else {TokenType::Greater},
+            '/' => {
                 if self.matched('/') {
+                    let doc = self.peek() == '/' && self.peek_next() != '/';
                     while self.peek() != '\n' && !self.is_at_end() {self.advance();}
-                } else {self.add_token2(TokenType::Slash);}
-            },
-            ' ' => (),
-            '\r' => (),
-            '\t' => (),
-            '\n' => self.line += 1,
-            '"' => {self.string()},
-
-            _ => {
-                if c.is_numeric() {
-                    self.number();
-                } else if self.is_alpha(c) {
-                    self.identifier();
+                    return doc.then(|| Ok(self.doc_comment(3, 0)));
+                } else if self.matched('*') {
+                    let doc = self.peek() == '*' && !matches!(self.peek_next(), '*' | '/');
+                    if let Err(e) = self.block_comment() {
+                        return Some(Err(e));
+                    }
+                    return doc.then(|| Ok(self.doc_comment(3, 2)));
+                } else if self.matched('=') {
+                    return Some(Ok(self.token(TokenType::SlashEqual, None)));
                 }
-                else {
-                    error(self.line.try_into().unwrap(), "Unexpected character.")
-                }
-            }
+                TokenType::Slash
+            },
+            ' ' | '\r' | '\t' => return None,
+            '\n' => {
+                self.line += 1;
+                return None;
+            },
+            '"' => return Some(self.string_segment(true)),
+            'r' if self.peek() == '"' => {
+                self.advance();
+                return Some(self.raw_string());
+            },
+            c if c.is_ascii_digit() => return Some(self.number(c)),
+            c if self.is_alpha(c) => return Some(Ok(self.identifier())),
+            _ => return Some(Err(self.error("Unexpected character."))),
+        };
+
+        Some(Ok(self.token(token_type, None)))
+    }
+
+    fn token(&self, token_type: TokenType, literal: Option<Literal>) -> Token<'src> {
+        Token {
+            token_type,
+            lexme: &self.source[self.start..self.current],
+            literal,
+            line: self.line,
+            leading_trivia: "",
+            trailing_trivia: "",
         }
     }
 
-    fn add_token(&mut self, token: TokenType, token_type: Option<Literal>) {
-        let text = &self.source[self.start..self.current];
-        self.tokens.push(Token { 
-            token_type: token, 
-            lexme: text.to_string(), 
-            literal: token_type, 
-            line: self.line 
-        });
+    fn attach_trivia(&mut self, mut token: Token<'src>) -> Token<'src> {
+        if !self.trivia {
+            return token;
+        }
+
+        token.leading_trivia = &self.source[self.trivia_start..self.start];
+
+        let trailing_start = self.current;
+        loop {
+            match self.peek() {
+                ' ' | '\t' | '\r' if !self.is_at_end() => { self.advance(); },
+                '/' if self.peek_next() == '/' && !self.source[self.current..].starts_with("///") => {
+                    while self.peek() != '\n' && !self.is_at_end() {self.advance();}
+                },
+                _ => break,
+            }
+        }
+        token.trailing_trivia = &self.source[trailing_start..self.current];
+
+        self.trivia_start = self.current;
+        token
     }
 
-    fn add_token2(&mut self, token: TokenType) {
-        let text = &self.source[self.start..self.current];
-        self.tokens.push(Token { 
-            token_type: token, 
-            lexme: text.to_string(), 
-            literal: None, 
-            line: self.line 
-        })
+    fn error(&self, message: &str) -> ScanError {
+        ScanError { line: self.line, message: message.to_string() }
     }
 
     fn matched(&mut self, expected: char) -> bool {
         if self.is_at_end() { return false; }
 
-        if self.source_chars[self.current] != expected {
-            return false ;   
+        if self.peek() != expected {
+            return false;
         }
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
-    fn identifier(&mut self) {
+    fn identifier(&mut self) -> Token<'src> {
         while self.is_alpha_numeric(self.peek()) { self.advance(); }
         let text = &self.source[self.start..self.current];
-        let t = *match self.map.get(text) {
-            Some(x) => x,
-            None => &TokenType::Identifier,
+        let token_type = KEYWORDS.get(text).copied().unwrap_or(TokenType::Identifier);
+
+        self.token(token_type, None)
+    }
+
+    // Strips the `prefix` and `suffix` comment markers from the lexeme.
+    fn doc_comment(&self, prefix: usize, suffix: usize) -> Token<'src> {
+        let text = &self.source[self.start + prefix..self.current - suffix];
+        self.token(TokenType::DocComment, Some(Literal::Str(Symbol::intern(text))))
+    }
+
+    // Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn block_comment(&mut self) -> Result<(), ScanError> {
+        let opening_line = self.line;
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(ScanError { line: opening_line, message: "Unterminated block comment.".to_string() });
+            }
+
+            match self.advance() {
+                '/' if self.matched('*') => depth += 1,
+                '*' if self.matched('/') => depth -= 1,
+                '\n' => self.line += 1,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Scans string contents up to the closing quote or the next `${`. `first`
+    // is false when resuming after the `}` of an interpolated expression.
+    fn string_segment(&mut self, first: bool) -> Result<Token<'src>, ScanError> {
+        let content_start = self.current;
+        let mut content_end;
+        // only allocated once an escape makes the value differ from the source
+        let mut decoded: Option<String> = None;
+        let mut escape_error = None;
+
+        let token_type = loop {
+            if self.is_at_end() {
+                return Err(self.error("Unterminated string."));
+            }
+
+            content_end = self.current;
+            match self.advance() {
+                '"' => break if first { TokenType::String } else { TokenType::InterpolationEnd },
+                '$' if self.matched('{') => {
+                    self.interpolations.push(0);
+                    break if first { TokenType::InterpolationStart } else { TokenType::InterpolationSegment };
+                },
+                '\\' => {
+                    let value = decoded.get_or_insert_with(|| self.source[content_start..content_end].to_string());
+                    match self.escape() {
+                        Ok(c) => value.push(c),
+                        Err(e) => { escape_error.get_or_insert(e); },
+                    }
+                },
+                c => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    if let Some(value) = decoded.as_mut() {
+                        value.push(c);
+                    }
+                },
+            }
         };
 
-        self.add_token(t, None);
+        if let Some(e) = escape_error {
+            return Err(e);
+        }
+
+        let value = match &decoded {
+            Some(value) => Symbol::intern(value),
+            None => Symbol::intern(&self.source[content_start..content_end]),
+        };
+        Ok(self.token(token_type, Some(Literal::Str(value))))
     }
 
-    fn string(&mut self) {
+    // Decodes the escape following a backslash.
+    fn escape(&mut self) -> Result<char, ScanError> {
+        if self.is_at_end() {
+            return Err(self.error("Unterminated string."));
+        }
+
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '0' => Ok('\0'),
+            '$' => Ok('$'),
+            'u' => self.unicode_escape(),
+            c => Err(self.error(&format!("Unknown escape sequence '\\{}'.", c))),
+        }
+    }
+
+    // `\u{XXXX}` with one to six hex digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self) -> Result<char, ScanError> {
+        if !self.matched('{') {
+            return Err(self.error("Expect '{' after '\\u'."));
+        }
+
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+
+        if !self.matched('}') {
+            return Err(self.error("Expect '}' after unicode escape digits."));
+        }
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(self.error("Unicode escape must have 1 to 6 hex digits."));
+        }
+
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        char::from_u32(code)
+            .ok_or_else(|| self.error(&format!("Invalid code point U+{:X} in unicode escape.", code)))
+    }
+
+    // Raw strings `r"..."` keep every character between the quotes as is.
+    fn raw_string(&mut self) -> Result<Token<'src>, ScanError> {
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
-                self.line+= 1;
+                self.line += 1;
             }
             self.advance();
         }
 
         if self.is_at_end() {
-            error(self.line.try_into().unwrap(), "Unterminated string.");
+            return Err(self.error("Unterminated raw string."));
         }
 
         self.advance();
 
-        let value: String = self.source[self.start+1..self.current-1].to_string();
-        self.add_token(TokenType::String, Some(Literal::Str(value)));
+        let value = Symbol::intern(&self.source[self.start + 2..self.current - 1]);
+        Ok(self.token(TokenType::String, Some(Literal::Str(value))))
     }
 
-    fn number(&mut self) {
-        while (self.peek().is_numeric() || self.peek() == '.') && !self.is_at_end() {
+    // Numbers are decimal (`12`, `1.5`, `1e-9`), hex (`0xFF`) or binary
+    // (`0b1010`), with single `_` separators allowed between digits.
+    fn number(&mut self, first: char) -> Result<Token<'src>, ScanError> {
+        let radix = if first == '0' && (self.matched('x') || self.matched('X')) {
+            16
+        } else if first == '0' && (self.matched('b') || self.matched('B')) {
+            2
+        } else {
+            self.current = self.start;
+            10
+        };
+
+        let mut result = if radix == 10 { self.decimal() } else { self.digits(radix) };
+        if result.is_ok() && (self.is_alpha_numeric(self.peek()) || self.is_fraction_dot()) {
+            result = Err(format!("Unexpected '{}'", self.peek()));
+        }
+
+        if let Err(message) = result {
+            // consume the rest of the malformed literal so it is reported once
+            while self.is_alpha_numeric(self.peek()) || self.is_fraction_dot() {
+                self.advance();
+            }
+            let text = &self.source[self.start..self.current];
+            return Err(self.error(&format!("{} in number literal '{}'.", message, text)));
+        }
+
+        let text = &self.source[self.start..self.current];
+        let text = if text.contains('_') { Cow::Owned(text.replace('_', "")) } else { Cow::Borrowed(text) };
+        let value = if radix == 10 {
+            text.parse::<f64>().ok()
+        } else {
+            u64::from_str_radix(&text[2..], radix).ok().map(|n| n as f64)
+        };
+
+        match value {
+            Some(n) => Ok(self.token(TokenType::Number, Some(Literal::Num(n)))),
+            None => Err(self.error(&format!("Number literal '{}' is too large.", text))),
+        }
+    }
+
+    fn decimal(&mut self) -> Result<(), String> {
+        self.digits(10)?;
+
+        if self.is_fraction_dot() {
+            self.advance();
+            self.digits(10)?;
+        }
+
+        if self.peek() == 'e' || self.peek() == 'E' {
             self.advance();
+            if self.peek() == '+' || self.peek() == '-' {
+                self.advance();
+            }
+            self.digits(10)?;
         }
-        
-        let value = self.source[self.start..self.current].to_string();
-        self.add_token(TokenType::Number, Some(Literal::Num(value.parse::<f64>().unwrap())));
+        Ok(())
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<(), String> {
+        let run_start = self.current;
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
+
+        let run = &self.source[run_start..self.current];
+        if run.is_empty() {
+            return Err("Missing digits".to_string());
+        }
+        if run.starts_with('_') || run.ends_with('_') || run.contains("__") {
+            return Err("Misplaced digit separator".to_string());
+        }
+        Ok(())
+    }
+
+    // A '.' only belongs to a number when a digit follows it, so `1.foo`
+    // still scans as a number followed by a dot.
+    fn is_fraction_dot(&self) -> bool {
+        self.peek() == '.' && self.peek_next().is_ascii_digit()
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() { return '\0'; }
-        self.source_chars[self.current]
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn peek_next(&self) -> char {
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn advance(&mut self) -> char {
-        let c = self.source_chars[self.current];
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
         c
     }
 
-    fn is_alpha(&mut self, c: char) -> bool {
-        c >= 'a' && c <= 'z' || c >= 'A' && c <= 'Z' || c == '_'
+    // Identifiers follow Unicode UAX #31: XID_Start or '_' followed by
+    // XID_Continue characters.
+    fn is_alpha(&self, c: char) -> bool {
+        c == '_' || c.is_xid_start()
+    }
+
+    fn is_alpha_numeric(&self, c: char) -> bool {
+        c.is_xid_continue()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Scanned<'src> = Result<(TokenType, &'src str, Option<Literal>), String>;
+
+    fn scan(source: &str) -> Vec<Scanned<'_>> {
+        Lexer::new(source)
+            .map(|result| result.map(|t| (t.token_type, t.lexme, t.literal)).map_err(|e| e.message))
+            .collect()
+    }
+
+    fn eof() -> Scanned<'static> {
+        Ok((TokenType::Eof, "", None))
     }
 
-    fn is_alpha_numeric(&mut self, c: char) -> bool {
-        self.is_alpha(c) || c.is_numeric()
+    // Scan errors are dropped, so their text has to come back as trivia.
+    #[test]
+    fn trivia_round_trip() {
+        let sources = [
+            "1 + 2 // sum\n/// doc\nfoo",
+            "a /* outer /* inner */ still */ b",
+            "x\r\n  y\r\n",
+            "1 @ 2 # 3",
+            "\"unterminated",
+            "\"a${b \"c\"} d",
+            "/* unterminated /* */",
+            "0b102 + é",
+            "",
+        ];
+        for source in sources {
+            let text: String = Lexer::with_trivia(source)
+                .filter_map(Result::ok)
+                .map(|t| format!("{}{}{}", t.leading_trivia, t.lexme, t.trailing_trivia))
+                .collect();
+            assert_eq!(text, source);
+        }
+    }
+
+    fn string<'src>(lexme: &'src str, value: &str) -> Scanned<'src> {
+        Ok((TokenType::String, lexme, Some(Literal::Str(Symbol::intern(value)))))
+    }
+
+    #[test]
+    fn string_escapes() {
+        let source = r#""a\tb\n\\\"\$\0\u{48}\u{1F600}""#;
+        assert_eq!(scan(source), vec![string(source, "a\tb\n\\\"$\0H\u{1F600}"), eof()]);
+    }
+
+    // The rest of the string is still consumed, so each case is one error.
+    #[test]
+    fn malformed_escapes() {
+        let cases = [
+            (r#""\q""#, "Unknown escape sequence '\\q'."),
+            (r#""a\qb\zc""#, "Unknown escape sequence '\\q'."),
+            (r#""\u41""#, "Expect '{' after '\\u'."),
+            (r#""\u{41""#, "Expect '}' after unicode escape digits."),
+            (r#""\u{}""#, "Unicode escape must have 1 to 6 hex digits."),
+            (r#""\u{1234567}""#, "Unicode escape must have 1 to 6 hex digits."),
+            (r#""\u{110000}""#, "Invalid code point U+110000 in unicode escape."),
+            (r#""\u{D800}""#, "Invalid code point U+D800 in unicode escape."),
+        ];
+        for (source, message) in cases {
+            assert_eq!(scan(source), vec![Err(message.to_string()), eof()], "{}", source);
+        }
     }
 
+    #[test]
+    fn raw_strings() {
+        let source = r#"r"a\n${b}""#;
+        assert_eq!(scan(source), vec![string(source, "a\\n${b}"), eof()]);
+        assert_eq!(scan("r\"a\nb\" x"), vec![
+            string("r\"a\nb\"", "a\nb"),
+            Ok((TokenType::Identifier, "x", None)),
+            eof(),
+        ]);
+        assert_eq!(scan(r#"r"abc"#), vec![Err("Unterminated raw string.".to_string()), eof()]);
+    }
+
+    #[test]
+    fn number_literals() {
+        assert_eq!(scan("0xFF"), vec![Ok((TokenType::Number, "0xFF", Some(Literal::Num(255.0)))), eof()]);
+        assert_eq!(scan("1e-9"), vec![Ok((TokenType::Number, "1e-9", Some(Literal::Num(1e-9)))), eof()]);
+    }
+
+    // A malformed literal is consumed whole and reported once.
+    #[test]
+    fn malformed_numbers() {
+        let cases = [
+            ("1.2.3", "Unexpected '.' in number literal '1.2.3'."),
+            ("0x", "Missing digits in number literal '0x'."),
+            ("0b102", "Unexpected '2' in number literal '0b102'."),
+            ("1__0", "Misplaced digit separator in number literal '1__0'."),
+            ("1_", "Misplaced digit separator in number literal '1_'."),
+            ("1e", "Missing digits in number literal '1e'."),
+        ];
+        for (source, message) in cases {
+            assert_eq!(scan(source), vec![Err(message.to_string()), eof()], "{}", source);
+        }
+    }
+
+    #[test]
+    fn dot_after_number() {
+        assert_eq!(scan("1.foo"), vec![
+            Ok((TokenType::Number, "1", Some(Literal::Num(1.0)))),
+            Ok((TokenType::Dot, ".", None)),
+            Ok((TokenType::Identifier, "foo", None)),
+            eof(),
+        ]);
+        assert_eq!(scan("0..10"), vec![
+            Ok((TokenType::Number, "0", Some(Literal::Num(0.0)))),
+            Ok((TokenType::DotDot, "..", None)),
+            Ok((TokenType::Number, "10", Some(Literal::Num(10.0)))),
+            eof(),
+        ]);
+    }
 }
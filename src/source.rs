@@ -0,0 +1,75 @@
+use std::{fmt, fs, io, path::Path};
+
+// A script ready for scanning. Loading strips a UTF-8 byte order mark and a
+// leading `#!` line, so scripts can be run directly as executables, and turns
+// CRLF line endings into LF. The shebang's newline is kept so line numbers
+// still match the file.
+pub struct SourceFile {
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io(io::Error),
+    InvalidUtf8 { offset: usize, line: usize },
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::InvalidUtf8 { offset, .. } => write!(f, "Invalid UTF-8 sequence at byte offset {}.", offset),
+        }
+    }
+}
+
+impl SourceFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<SourceFile, SourceError> {
+        let bytes = fs::read(path).map_err(SourceError::Io)?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<SourceFile, SourceError> {
+        let mut text = String::from_utf8(bytes).map_err(|e| {
+            let offset = e.utf8_error().valid_up_to();
+            let line = e.as_bytes()[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+            SourceError::InvalidUtf8 { offset, line }
+        })?;
+
+        if text.starts_with('\u{feff}') {
+            text.replace_range(..'\u{feff}'.len_utf8(), "");
+        }
+        if text.starts_with("#!") {
+            let end = text.find('\n').unwrap_or(text.len());
+            text.replace_range(..end, "");
+        }
+        if text.contains('\r') {
+            text = text.replace("\r\n", "\n");
+        }
+
+        Ok(SourceFile { text })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8]) -> String {
+        SourceFile::from_bytes(bytes.to_vec()).unwrap().text
+    }
+
+    #[test]
+    fn strips_bom_shebang_and_crlf() {
+        assert_eq!(text(b"\xef\xbb\xbf#!/usr/bin/env rlox\r\n1 +\r\n2\r\n"), "\n1 +\n2\n");
+        assert_eq!(text(b"#!/usr/bin/env rlox"), "");
+        assert_eq!(text(b"1 # 2\r\n#!"), "1 # 2\n#!");
+    }
+
+    // The offset counts bytes of the file as given, BOM included.
+    #[test]
+    fn invalid_utf8_position() {
+        let result = SourceFile::from_bytes(b"\xef\xbb\xbf1\n2 +\n\"\xff\"".to_vec());
+        assert!(matches!(result, Err(SourceError::InvalidUtf8 { offset: 10, line: 3 })), "{:?}", result.err());
+    }
+}